@@ -0,0 +1,321 @@
+//! Runs the simulation across a grid of starting parameters instead of a
+//! single hard-coded [`crate::Game::new`] config, so a sweep over `p_heads`,
+//! `multiplier`, `flip_time`, and `coin_val` can be explored in one pass.
+
+use std::path::PathBuf;
+
+use chrono::Local;
+use serde::Serialize;
+
+use crate::upgrades::UpgradePolicy;
+use crate::{Game, GameOutcome, GameParams};
+
+/// One axis of a parameter sweep: either evenly spaced in linear space, or
+/// evenly spaced in log10-space.
+#[derive(Debug, Clone, Copy)]
+pub enum AxisSpec {
+    /// `n` evenly spaced values between `start` and `stop`, inclusive.
+    Linear { start: f64, stop: f64, n: usize },
+
+    /// `n` values spaced evenly in log10-space between `10^start` and `10^stop`.
+    Log { start: f64, stop: f64, n: usize },
+}
+
+impl AxisSpec {
+    /// Materialize this axis into its concrete grid values.
+    fn values(self) -> Vec<f64> {
+        match self {
+            AxisSpec::Linear { start, stop, n } => linspace(start, stop, n),
+            AxisSpec::Log { start, stop, n } => logspace(start, stop, n),
+        }
+    }
+}
+
+/// `n` evenly spaced values from `a` to `b`, inclusive of both endpoints.
+///
+/// # Panics
+///
+/// Panics if `n < 2`, since there is no way to include both endpoints otherwise.
+#[allow(clippy::cast_precision_loss)]
+pub fn linspace(a: f64, b: f64, n: usize) -> Vec<f64> {
+    assert!(
+        n >= 2,
+        "linspace needs at least 2 points to include both endpoints"
+    );
+    let step = (b - a) / (n - 1) as f64;
+    (0..n).map(|i| a + step * i as f64).collect()
+}
+
+/// `n` values spaced evenly in log10-space between `10^a` and `10^b`, inclusive.
+///
+/// # Panics
+///
+/// Panics if `n < 2`, for the same reason as [`linspace`].
+pub fn logspace(a: f64, b: f64, n: usize) -> Vec<f64> {
+    linspace(a, b, n)
+        .into_iter()
+        .map(|x| 10f64.powf(x))
+        .collect()
+}
+
+/// The grid of starting parameters to sweep over.
+#[derive(Debug, Clone, Copy)]
+pub struct SweepGrid {
+    pub p_heads: AxisSpec,
+    pub multiplier: AxisSpec,
+    pub flip_time: AxisSpec,
+    pub coin_val: AxisSpec,
+}
+
+impl SweepGrid {
+    /// The Cartesian product of this grid's axes, as a flat list of starting
+    /// parameter combinations ready to play.
+    fn cells(self) -> Vec<GameParams> {
+        let p_heads_vals = self.p_heads.values();
+        let multiplier_vals = self.multiplier.values();
+        let flip_time_vals = self.flip_time.values();
+        let coin_val_vals = self.coin_val.values();
+
+        let mut cells = Vec::with_capacity(
+            p_heads_vals.len() * multiplier_vals.len() * flip_time_vals.len() * coin_val_vals.len(),
+        );
+        for &p_heads in &p_heads_vals {
+            for &multiplier in &multiplier_vals {
+                for &flip_time in &flip_time_vals {
+                    for &coin_val in &coin_val_vals {
+                        cells.push(GameParams {
+                            p_heads,
+                            flip_time,
+                            coin_val,
+                            multiplier,
+                        });
+                    }
+                }
+            }
+        }
+        cells
+    }
+}
+
+/// One row of the long-format sweep output: the starting parameters for a
+/// grid cell, plus its aggregated `n_flips`/`cash` outcome statistics, as
+/// scalar fields rather than nested in their own struct -- see
+/// `GameRecord` in `main.rs` for why `csv` requires that.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct SweepRow {
+    pub p_heads: f64,
+    pub multiplier: f64,
+    pub flip_time: f64,
+    pub coin_val: f64,
+    pub mean_n_flips: f64,
+    pub median_n_flips: f64,
+    pub p90_n_flips: f64,
+    pub mean_cash: f64,
+    pub median_cash: f64,
+    pub p90_cash: f64,
+}
+
+/// The `pct` percentile (0.0..=100.0) of `values`, linearly interpolating
+/// between the two nearest ranks. `values` is sorted in place.
+///
+/// # Panics
+///
+/// Panics if `values` is empty.
+fn percentile(values: &mut [f64], pct: f64) -> f64 {
+    assert!(!values.is_empty(), "percentile needs at least 1 value");
+    values.sort_by(f64::total_cmp);
+    let n = values.len();
+    if n == 1 {
+        return values[0];
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    let rank = (pct / 100.0) * (n - 1) as f64;
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let (lo, hi) = (rank.floor() as usize, rank.ceil() as usize);
+    if lo == hi {
+        values[lo]
+    } else {
+        #[allow(clippy::cast_precision_loss)]
+        let frac = rank - lo as f64;
+        values[lo] + frac * (values[hi] - values[lo])
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Run `n_games` simulations at every cell of `grid`, aggregating `n_flips`
+/// and `cash` into mean/median/p90 summaries, and return one row per cell.
+///
+/// # Panics
+///
+/// Panics if `n_games == 0`, since there would be no outcomes left to
+/// aggregate into a mean/median/p90.
+#[allow(clippy::cast_precision_loss)]
+pub fn run_sweep(
+    grid: SweepGrid,
+    n_win: usize,
+    max_iters: usize,
+    n_games: usize,
+    policy: &dyn UpgradePolicy,
+) -> Vec<SweepRow> {
+    assert!(
+        n_games >= 1,
+        "run_sweep needs at least 1 game per cell to aggregate"
+    );
+
+    grid.cells()
+        .into_iter()
+        .map(|params| {
+            let mut n_flips = Vec::with_capacity(n_games);
+            let mut cash = Vec::with_capacity(n_games);
+
+            for _ in 0..n_games {
+                let (flips, final_cash) =
+                    match Game::from_params(params).play(n_win, max_iters, policy) {
+                        GameOutcome::Finished(g) => (g.n_flips(), g.cash()),
+                        GameOutcome::Unfinished { state, iters_run } => (iters_run, state.cash()),
+                    };
+                n_flips.push(flips as f64);
+                cash.push(final_cash);
+            }
+
+            SweepRow {
+                p_heads: params.p_heads,
+                multiplier: params.multiplier,
+                flip_time: params.flip_time,
+                coin_val: params.coin_val,
+                mean_n_flips: mean(&n_flips),
+                median_n_flips: percentile(&mut n_flips, 50.0),
+                p90_n_flips: percentile(&mut n_flips, 90.0),
+                mean_cash: mean(&cash),
+                median_cash: percentile(&mut cash, 50.0),
+                p90_cash: percentile(&mut cash, 90.0),
+            }
+        })
+        .collect()
+}
+
+/// Saves `rows` to a long-format TSV file named `<YYYY-MM-DDTHH-MM-SS>_sweep.tsv`
+/// in the current directory. Returns the created file path.
+///
+/// # Errors
+///
+/// Will error out if the file cannot be created, or if there is an error during writing,
+/// or if the file cannot be flushed.
+pub fn save_sweep_tsv(rows: &[SweepRow]) -> std::io::Result<PathBuf> {
+    let timestamp = Local::now().format("%Y-%m-%dT%H-%M-%S").to_string();
+    let filename = format!("{timestamp}_sweep.tsv");
+    let path = PathBuf::from(&filename);
+
+    let mut wtr = csv::WriterBuilder::new()
+        .delimiter(b'\t')
+        .from_path(&path)?;
+
+    for row in rows {
+        wtr.serialize(row)?;
+    }
+
+    wtr.flush()?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(0.0, 1.0, 5, vec![0.0, 0.25, 0.5, 0.75, 1.0])]
+    #[case(1.0, 3.0, 3, vec![1.0, 2.0, 3.0])]
+    #[case(-1.0, 1.0, 2, vec![-1.0, 1.0])]
+    fn test_linspace(#[case] a: f64, #[case] b: f64, #[case] n: usize, #[case] expected: Vec<f64>) {
+        let got = linspace(a, b, n);
+        assert_eq!(got.len(), expected.len());
+        for (g, e) in got.iter().zip(expected.iter()) {
+            assert_relative_eq!(g, e, epsilon = 1e-9);
+        }
+    }
+
+    #[rstest]
+    #[case(0.0, 2.0, 3, vec![1.0, 10.0, 100.0])]
+    fn test_logspace(#[case] a: f64, #[case] b: f64, #[case] n: usize, #[case] expected: Vec<f64>) {
+        let got = logspace(a, b, n);
+        assert_eq!(got.len(), expected.len());
+        for (g, e) in got.iter().zip(expected.iter()) {
+            assert_relative_eq!(g, e, epsilon = 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_cartesian_product_size() {
+        let grid = SweepGrid {
+            p_heads: AxisSpec::Linear {
+                start: 0.1,
+                stop: 0.5,
+                n: 5,
+            },
+            multiplier: AxisSpec::Linear {
+                start: 1.0,
+                stop: 2.0,
+                n: 2,
+            },
+            flip_time: AxisSpec::Linear {
+                start: 1.0,
+                stop: 1.0,
+                n: 2,
+            },
+            coin_val: AxisSpec::Log {
+                start: -3.0,
+                stop: -1.0,
+                n: 3,
+            },
+        };
+        assert_eq!(grid.cells().len(), 5 * 2 * 2 * 3);
+    }
+
+    #[test]
+    fn save_sweep_tsv_writes_a_readable_header_and_row() {
+        let grid = SweepGrid {
+            p_heads: AxisSpec::Linear {
+                start: 0.2,
+                stop: 0.3,
+                n: 2,
+            },
+            multiplier: AxisSpec::Linear {
+                start: 1.5,
+                stop: 1.5,
+                n: 2,
+            },
+            flip_time: AxisSpec::Linear {
+                start: 1.0,
+                stop: 1.0,
+                n: 2,
+            },
+            coin_val: AxisSpec::Linear {
+                start: 0.01,
+                stop: 0.01,
+                n: 2,
+            },
+        };
+        let rows = run_sweep(grid, 5, 1_000, 10, &crate::upgrades::GreedyPolicy);
+
+        let path =
+            save_sweep_tsv(&rows).expect("serializing SweepRow's flattened stats should succeed");
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let mut lines = contents.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "p_heads\tmultiplier\tflip_time\tcoin_val\tmean_n_flips\tmedian_n_flips\t\
+             p90_n_flips\tmean_cash\tmedian_cash\tp90_cash"
+        );
+        assert_eq!(lines.count(), rows.len());
+    }
+}
@@ -1,5 +1,7 @@
 use serde::Serialize;
 
+use crate::Game;
+
 /// A struct for keeping track of the upgrades currently
 /// active. Points to an index in constant arrays.
 #[derive(Debug, Clone, Copy, Serialize)]
@@ -17,14 +19,47 @@ impl PHeadsUpgradeState {
     pub fn can_upgrade(&self) -> bool {
         self.p_heads_idx < (PHEADS_UPGRADES.len() - 1)
     }
+
+    /// The next upgrade to `p_heads` that's available to purchase, if any.
+    pub fn next_upgrade(&self) -> Option<&'static PHeadsUpgrade> {
+        self.can_upgrade()
+            .then(|| &PHEADS_UPGRADES[self.p_heads_idx + 1])
+    }
+
+    /// Move on to the upgrade returned by the most recent `next_upgrade` call.
+    pub fn advance(&mut self) {
+        self.p_heads_idx += 1;
+    }
+
+    /// The index of the upgrade this state currently sits at.
+    pub fn p_heads_idx(&self) -> usize {
+        self.p_heads_idx
+    }
+
+    /// Restore a state that previously reached upgrade `idx`.
+    pub fn at_idx(idx: usize) -> Self {
+        PHeadsUpgradeState { p_heads_idx: idx }
+    }
 }
 
 /// A struct for managing each possible upgrade to the probability of heads.
-struct PHeadsUpgrade {
+pub struct PHeadsUpgrade {
     prob: f64,
     cost: f64,
 }
 
+impl PHeadsUpgrade {
+    /// The `p_heads` this upgrade grants once purchased.
+    pub fn prob(&self) -> f64 {
+        self.prob
+    }
+
+    /// How much cash this upgrade costs to purchase.
+    pub fn cost(&self) -> f64 {
+        self.cost
+    }
+}
+
 /// The array of available upgrades
 static PHEADS_UPGRADES: [PHeadsUpgrade; 9] = [
     PHeadsUpgrade {
@@ -65,6 +100,54 @@ static PHEADS_UPGRADES: [PHeadsUpgrade; 9] = [
     },
 ];
 
+/// Decides, after each flip, whether a game should spend its `cash` on the
+/// next available `p_heads` upgrade.
+pub trait UpgradePolicy: Send + Sync {
+    /// A short, stable name for this policy, recorded onto the `Game` that
+    /// plays with it.
+    fn name(&self) -> &'static str;
+
+    /// Should `next` be purchased right now, given the game's current state?
+    fn should_buy(&self, game: &Game, next: &PHeadsUpgrade) -> bool;
+}
+
+/// Buys every upgrade the instant it becomes affordable.
+pub struct GreedyPolicy;
+
+impl UpgradePolicy for GreedyPolicy {
+    fn name(&self) -> &'static str {
+        "greedy"
+    }
+
+    fn should_buy(&self, game: &Game, next: &PHeadsUpgrade) -> bool {
+        game.cash() >= next.cost()
+    }
+}
+
+/// Buys aggressively early in the game, but raises its affordability bar as
+/// `total_time` grows towards `time_budget`, so it stops sinking cash into
+/// marginal late-game upgrades. Mirrors a reward-annealing schedule: the
+/// threshold for "worth it" rises with `1.0 + anneal_factor * progress`.
+pub struct AnnealedPolicy {
+    /// How steeply the affordability threshold rises as `progress` nears 1.
+    pub anneal_factor: f64,
+
+    /// The `total_time` at which `progress` reaches 1.0.
+    pub time_budget: f64,
+}
+
+impl UpgradePolicy for AnnealedPolicy {
+    fn name(&self) -> &'static str {
+        "annealed"
+    }
+
+    fn should_buy(&self, game: &Game, next: &PHeadsUpgrade) -> bool {
+        let progress = (game.total_time() / self.time_budget).clamp(0.0, 1.0);
+        let threshold = next.cost() * (1.0 + self.anneal_factor * progress);
+        game.cash() >= threshold
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -84,4 +167,50 @@ mod tests {
         let up = PHeadsUpgradeState { p_heads_idx: idx };
         assert_eq!(expected, up.can_upgrade());
     }
+
+    /// Build a `Game` sitting at upgrade index 0 (`p_heads` 0.20) with `cash`
+    /// and `total_time` set directly, so a purchasing decision can be tested
+    /// without playing out flips to get there.
+    fn game_at(cash: f64, total_time: f64) -> Game {
+        Game::from_raw_parts(0.20, 1.0, total_time, 0, 0, 0.01, 1.5, cash, 0, "none", 0)
+    }
+
+    #[test]
+    fn greedy_buys_the_instant_it_is_affordable() {
+        let mut game = game_at(0.01, 0.0);
+        game.maybe_buy_upgrade(&GreedyPolicy);
+        assert_eq!(game.upgrade_idx(), 1);
+        assert_eq!(game.p_heads(), PHEADS_UPGRADES[1].prob());
+        assert_eq!(game.cash(), 0.0);
+        assert_eq!(game.n_upgrades_bought(), 1);
+    }
+
+    #[test]
+    fn greedy_holds_off_until_affordable() {
+        let mut game = game_at(0.005, 0.0);
+        game.maybe_buy_upgrade(&GreedyPolicy);
+        assert_eq!(game.upgrade_idx(), 0);
+        assert_eq!(game.cash(), 0.005);
+        assert_eq!(game.n_upgrades_bought(), 0);
+    }
+
+    #[test]
+    fn annealed_threshold_scales_with_progress() {
+        let policy = AnnealedPolicy {
+            anneal_factor: 2.0,
+            time_budget: 100.0,
+        };
+        // Early on, progress is near 0 so the threshold is just the cost --
+        // the same purchase a GreedyPolicy would make.
+        let mut early = game_at(0.01, 0.0);
+        early.maybe_buy_upgrade(&policy);
+        assert_eq!(early.upgrade_idx(), 1);
+
+        // At progress 1.0 the threshold has risen to cost * (1 + anneal_factor),
+        // so the same cash is no longer enough.
+        let mut late = game_at(0.01, 100.0);
+        late.maybe_buy_upgrade(&policy);
+        assert_eq!(late.upgrade_idx(), 0);
+        assert_eq!(late.cash(), 0.01);
+    }
 }